@@ -0,0 +1,39 @@
+//! Space-level permissions shared by every pallet that gates a dispatchable on what a space's
+//! owner has delegated to its editors, moderators, or other roles.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Encode, Decode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+use sp_runtime::RuntimeDebug;
+
+/// A single space-scoped capability that can be granted to a role and checked via
+/// `pallet_spaces::Module::ensure_account_has_space_permission`.
+#[derive(Encode, Decode, Clone, Copy, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum SpacePermission {
+	ManageRoles,
+	RepresentSpaceInternally,
+	RepresentSpaceExternally,
+	UpdateSpace,
+
+	CreatePosts,
+	UpdateOwnPosts,
+	UpdateAnyPost,
+	DeleteOwnPosts,
+	DeleteAnyPost,
+
+	CreateComments,
+	UpdateOwnComments,
+	DeleteOwnComments,
+
+	Upvote,
+	Downvote,
+	Share,
+
+	/// Create, update, and delete a space's `pallet_subscriptions` plans, and redirect the
+	/// plans' recipient wallet — kept independent of [`SpacePermission::UpdateSpace`] so an
+	/// owner can delegate billing administration without also handing out space-settings edit.
+	ManageSubscriptionPlans,
+}
@@ -0,0 +1,76 @@
+//! Runtime API definition for the subscriptions pallet, consumed by `pallets/subscriptions/rpc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Codec, Encode, Decode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+use sp_runtime::RuntimeDebug;
+use sp_std::prelude::*;
+
+use pallet_subscriptions::{BalanceOf, Subscription, SubscriptionId, SubscriptionPlan, SubscriptionPlanId};
+use pallet_utils::{Content, SpaceId};
+
+/// A flattened, RPC-friendly view of a `SubscriptionPlan`.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct FlatSubscriptionPlan<AccountId, Balance> {
+	pub id: SubscriptionPlanId,
+	pub space_id: SpaceId,
+	pub wallet: Option<AccountId>,
+	pub price: Balance,
+	pub content: Content,
+	pub is_active: bool,
+}
+
+/// A flattened, RPC-friendly view of a `Subscription`.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct FlatSubscription<AccountId, BlockNumber> {
+	pub id: SubscriptionId,
+	pub plan_id: SubscriptionPlanId,
+	pub wallet: Option<AccountId>,
+	pub is_active: bool,
+	pub next_charge_at: BlockNumber,
+}
+
+impl<T: pallet_subscriptions::Trait> From<SubscriptionPlan<T>> for FlatSubscriptionPlan<T::AccountId, BalanceOf<T>> {
+	fn from(plan: SubscriptionPlan<T>) -> Self {
+		FlatSubscriptionPlan {
+			id: plan.id,
+			space_id: plan.space_id,
+			wallet: plan.wallet,
+			price: plan.price,
+			content: plan.content,
+			is_active: plan.is_active,
+		}
+	}
+}
+
+impl<T: pallet_subscriptions::Trait> From<Subscription<T>> for FlatSubscription<T::AccountId, T::BlockNumber> {
+	fn from(subscription: Subscription<T>) -> Self {
+		FlatSubscription {
+			id: subscription.id,
+			plan_id: subscription.plan_id,
+			wallet: subscription.wallet,
+			is_active: subscription.is_active,
+			next_charge_at: subscription.next_charge_at,
+		}
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	/// The read side of the subscriptions pallet: enumerate a space's plans, a patron's
+	/// subscriptions, and preview when a subscription will next be charged.
+	pub trait SubscriptionsApi<AccountId, Balance, BlockNumber> where
+		AccountId: Codec,
+		Balance: Codec,
+		BlockNumber: Codec,
+	{
+		fn get_plans_by_space(space_id: SpaceId) -> Vec<FlatSubscriptionPlan<AccountId, Balance>>;
+
+		fn get_subscriptions_by_patron(account: AccountId) -> Vec<FlatSubscription<AccountId, BlockNumber>>;
+
+		fn get_next_charge(subscription_id: SubscriptionId) -> Option<BlockNumber>;
+	}
+}
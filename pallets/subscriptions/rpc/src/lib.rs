@@ -0,0 +1,116 @@
+//! RPC interface for the subscriptions pallet.
+//!
+//! This crate only provides the jsonrpc-facing trait and its `ProvideRuntimeApi`-backed
+//! implementation. Wiring it up end to end additionally requires, in the runtime and node
+//! crates (outside `pallets/subscriptions`): implementing `SubscriptionsRuntimeApi` in the
+//! runtime's `impl_runtime_apis!` block (delegating to `Module::get_plans_by_space` etc. via
+//! the `From` conversions in `pallet_subscriptions_rpc_runtime_api`), and constructing
+//! `Subscriptions::new` into the node's RPC extension builder alongside the other pallets'
+//! RPCs.
+
+use std::sync::Arc;
+
+use codec::Codec;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result as RpcResult};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+pub use pallet_subscriptions_rpc_runtime_api::{
+	FlatSubscription, FlatSubscriptionPlan, SubscriptionsApi as SubscriptionsRuntimeApi,
+};
+use pallet_subscriptions::SubscriptionId;
+use pallet_utils::SpaceId;
+
+#[rpc]
+pub trait SubscriptionsApi<BlockHash, AccountId, Balance, BlockNumber> {
+	#[rpc(name = "subscriptions_getPlansBySpace")]
+	fn get_plans_by_space(
+		&self,
+		space_id: SpaceId,
+		at: Option<BlockHash>
+	) -> RpcResult<Vec<FlatSubscriptionPlan<AccountId, Balance>>>;
+
+	#[rpc(name = "subscriptions_getSubscriptionsByPatron")]
+	fn get_subscriptions_by_patron(
+		&self,
+		account: AccountId,
+		at: Option<BlockHash>
+	) -> RpcResult<Vec<FlatSubscription<AccountId, BlockNumber>>>;
+
+	#[rpc(name = "subscriptions_getNextCharge")]
+	fn get_next_charge(
+		&self,
+		subscription_id: SubscriptionId,
+		at: Option<BlockHash>
+	) -> RpcResult<Option<BlockNumber>>;
+}
+
+/// A struct that implements the [`SubscriptionsApi`].
+pub struct Subscriptions<C, M> {
+	client: Arc<C>,
+	_marker: std::marker::PhantomData<M>,
+}
+
+impl<C, M> Subscriptions<C, M> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: Default::default() }
+	}
+}
+
+fn runtime_error(message: &str) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(1),
+		message: message.to_owned(),
+		data: None,
+	}
+}
+
+impl<C, Block, AccountId, Balance, BlockNumber>
+	SubscriptionsApi<<Block as BlockT>::Hash, AccountId, Balance, BlockNumber>
+	for Subscriptions<C, Block>
+where
+	Block: BlockT,
+	C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+	C::Api: SubscriptionsRuntimeApi<Block, AccountId, Balance, BlockNumber>,
+	AccountId: Codec,
+	Balance: Codec,
+	BlockNumber: Codec,
+{
+	fn get_plans_by_space(
+		&self,
+		space_id: SpaceId,
+		at: Option<<Block as BlockT>::Hash>
+	) -> RpcResult<Vec<FlatSubscriptionPlan<AccountId, Balance>>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.get_plans_by_space(&at, space_id)
+			.map_err(|e| runtime_error(&format!("Unable to get plans by space: {:?}", e)))
+	}
+
+	fn get_subscriptions_by_patron(
+		&self,
+		account: AccountId,
+		at: Option<<Block as BlockT>::Hash>
+	) -> RpcResult<Vec<FlatSubscription<AccountId, BlockNumber>>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.get_subscriptions_by_patron(&at, account)
+			.map_err(|e| runtime_error(&format!("Unable to get subscriptions by patron: {:?}", e)))
+	}
+
+	fn get_next_charge(
+		&self,
+		subscription_id: SubscriptionId,
+		at: Option<<Block as BlockT>::Hash>
+	) -> RpcResult<Option<BlockNumber>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		api.get_next_charge(&at, subscription_id)
+			.map_err(|e| runtime_error(&format!("Unable to get next charge: {:?}", e)))
+	}
+}
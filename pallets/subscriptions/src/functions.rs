@@ -0,0 +1,356 @@
+use frame_support::dispatch::{DispatchError, DispatchResult};
+use frame_support::traits::{Currency, ExistenceRequirement, Get};
+use frame_support::weights::Weight;
+
+use pallet_spaces::{Module as Spaces, Space};
+use pallet_utils::{SpaceId, WhoAndWhen};
+use sp_arithmetic::helpers_128bit::multiply_by_rational;
+use sp_runtime::traits::{AccountIdConversion, Saturating, SaturatedConversion, Zero};
+
+use super::*;
+
+impl<T: Trait> SubscriptionPlan<T> {
+	pub fn new(
+		id: SubscriptionPlanId,
+		created_by: T::AccountId,
+		space_id: SpaceId,
+		wallet: Option<T::AccountId>,
+		price: BalanceOf<T>,
+		period: SubscriptionPeriod<T::BlockNumber>,
+		content: Content,
+		release_condition: ReleaseCondition<T::AccountId, T::BlockNumber>,
+		refunds_enabled: bool,
+	) -> Self {
+		SubscriptionPlan {
+			id,
+			created: WhoAndWhen::<T>::new(created_by),
+			updated: None,
+			space_id,
+			wallet,
+			price,
+			period,
+			content,
+			is_active: true,
+			release_condition,
+			refunds_enabled,
+		}
+	}
+}
+
+impl<T: Trait> Subscription<T> {
+	pub fn new(
+		id: SubscriptionId,
+		created_by: T::AccountId,
+		wallet: Option<T::AccountId>,
+		plan_id: SubscriptionPlanId,
+		next_charge_at: T::BlockNumber,
+	) -> Self {
+		Subscription {
+			id,
+			created: WhoAndWhen::<T>::new(created_by),
+			updated: None,
+			wallet,
+			plan_id,
+			is_active: true,
+			next_charge_at,
+		}
+	}
+
+	pub fn ensure_subscriber(&self, who: &T::AccountId) -> DispatchResult {
+		ensure!(self.created.account == *who, Error::<T>::NotSubscriber);
+		Ok(())
+	}
+}
+
+impl<T: Trait> Module<T> {
+	pub fn require_plan(plan_id: SubscriptionPlanId) -> Result<SubscriptionPlan<T>, DispatchError> {
+		Ok(Self::plan_by_id(plan_id).ok_or(Error::<T>::SubscriptionPlanNotFound)?)
+	}
+
+	pub fn require_subscription(subscription_id: SubscriptionId) -> Result<Subscription<T>, DispatchError> {
+		Ok(Self::subscription_by_id(subscription_id).ok_or(Error::<T>::SubscriptionNotFound)?)
+	}
+
+	pub fn require_pending_release(subscription_id: SubscriptionId) -> Result<PendingRelease<T>, DispatchError> {
+		Ok(Self::pending_release_by_subscription(subscription_id).ok_or(Error::<T>::NoPendingRelease)?)
+	}
+
+	/// Authorizes plan and wallet management (`create_plan`, `update_plan`, `delete_plan`,
+	/// `update_space_default_wallet`) for anyone the space has delegated the
+	/// `ManageSubscriptionPlans` permission to, not only the space owner. A dedicated
+	/// permission, rather than reusing `UpdateSpace`, so a space owner can delegate billing
+	/// administration to an editor without also granting them space-settings edit.
+	pub fn ensure_subscriptions_manager(who: T::AccountId, space: &Space<T>) -> DispatchResult {
+		Spaces::<T>::ensure_account_has_space_permission(
+			who,
+			space,
+			SpacePermission::ManageSubscriptionPlans,
+			Error::<T>::NoPermissionToUpdateSubscriptionPlan.into()
+		)
+	}
+
+	/// Converts a [`SubscriptionPeriod`] into a number of blocks, using `BlocksPerDay`
+	/// as the base unit so that periods map deterministically onto block height.
+	pub fn period_in_blocks(period: &SubscriptionPeriod<T::BlockNumber>) -> T::BlockNumber {
+		let days_per_period: u32 = match period {
+			SubscriptionPeriod::Daily => 1,
+			SubscriptionPeriod::Weekly => 7,
+			SubscriptionPeriod::Quarterly => 91,
+			SubscriptionPeriod::Yearly => 365,
+			SubscriptionPeriod::Custom(blocks) => return *blocks,
+		};
+
+		T::BlocksPerDay::get().saturating_mul(days_per_period.into())
+	}
+
+	/// Resolves the wallet subscription fees should be withdrawn from: the subscription's
+	/// own custom wallet, falling back to the patron's default wallet, falling back to the
+	/// subscriber account itself.
+	pub fn resolve_patron_wallet(subscription: &Subscription<T>) -> T::AccountId {
+		subscription.wallet.clone()
+			.or_else(|| Self::patron_wallet(&subscription.created.account))
+			.unwrap_or_else(|| subscription.created.account.clone())
+	}
+
+	/// Resolves the wallet subscription fees should be transferred to. If the space has
+	/// a multisig configured, revenue accrues to its pallet-controlled revenue account
+	/// instead, to be withdrawn later via `propose_withdrawal`/`confirm_withdrawal`.
+	/// Otherwise falls back to the plan's own custom wallet, then the space's default
+	/// recipient wallet, then the space owner's account.
+	pub fn resolve_recipient(plan: &SubscriptionPlan<T>) -> Option<T::AccountId> {
+		if Self::recipient_multisig(plan.space_id).is_some() {
+			return Some(Self::revenue_account(plan.space_id));
+		}
+
+		plan.wallet.clone()
+			.or_else(|| Self::recipient_wallet(plan.space_id))
+			.or_else(|| Spaces::<T>::require_space(plan.space_id).map(|space: Space<T>| space.owner).ok())
+	}
+
+	/// Derives a deterministic, pallet-controlled account that accumulates a space's
+	/// subscription revenue when `RecipientMultisig` is configured for it.
+	pub fn revenue_account(space_id: SpaceId) -> T::AccountId {
+		T::ModuleId::get().into_sub_account(("revenue", space_id))
+	}
+
+	/// Computes `price * remaining / period`, the unused, prorated fraction of a
+	/// subscription's current period, used to refund an early `unsubscribe`. Uses
+	/// `multiply_by_rational` rather than a plain `mul` then `div` so a large `price *
+	/// remaining` can't silently saturate and overpay the refund out of the recipient's funds.
+	pub fn prorated_amount(price: BalanceOf<T>, remaining: T::BlockNumber, period: T::BlockNumber) -> BalanceOf<T> {
+		if period.is_zero() {
+			return Zero::zero();
+		}
+
+		let remaining: u128 = remaining.saturated_into();
+		let period: u128 = period.saturated_into();
+		let price: u128 = price.saturated_into();
+
+		multiply_by_rational(price, remaining, period).unwrap_or(0).saturated_into()
+	}
+
+	/// Inserts `subscription_id` into the due-subscriptions index at `charge_at`.
+	pub fn schedule_subscription(subscription_id: SubscriptionId, charge_at: T::BlockNumber) {
+		DueSubscriptionsAt::<T>::mutate(charge_at, |ids| ids.push(subscription_id));
+	}
+
+	/// Derives a deterministic, pallet-controlled escrow account for a subscription,
+	/// used to hold payments pending release under a [`ReleaseCondition`].
+	pub fn escrow_account(subscription_id: SubscriptionId) -> T::AccountId {
+		T::ModuleId::get().into_sub_account(subscription_id)
+	}
+
+	/// Settles a subscription payment of `price` from `patron`: paid straight to
+	/// `recipient` if the plan releases immediately, or moved into the subscription's
+	/// escrow account and accumulated onto any existing `PendingReleaseBySubscription`
+	/// otherwise, so recurring charges into escrow don't lose track of earlier periods.
+	pub fn settle_payment(
+		subscription_id: SubscriptionId,
+		patron: &T::AccountId,
+		recipient: T::AccountId,
+		price: BalanceOf<T>,
+		condition: ReleaseCondition<T::AccountId, T::BlockNumber>,
+	) -> DispatchResult {
+		let is_escrowed = condition != ReleaseCondition::Immediate;
+		let destination = if is_escrowed { Self::escrow_account(subscription_id) } else { recipient.clone() };
+
+		<T as pallet_utils::Trait>::Currency::transfer(
+			patron,
+			&destination,
+			price,
+			ExistenceRequirement::KeepAlive
+		)?;
+
+		if is_escrowed {
+			PendingReleaseBySubscription::<T>::mutate(subscription_id, |maybe_pending| {
+				match maybe_pending {
+					Some(pending) => {
+						pending.amount = pending.amount.saturating_add(price);
+						pending.recipient = recipient;
+						pending.condition = condition;
+					},
+					None => *maybe_pending = Some(PendingRelease::<T> { amount: price, recipient, condition }),
+				}
+			});
+		}
+
+		Ok(())
+	}
+
+	/// Refunds the prorated, unused portion of a cancelled subscription's current period.
+	/// Draws from the subscription's escrow account (shrinking its `PendingReleaseBySubscription`
+	/// entry, since escrow may still hold earlier periods' payments) when one exists, or from
+	/// the plan's resolved recipient otherwise. Best-effort: a failed transfer (e.g. the
+	/// recipient can no longer receive funds) must not revert the cancellation itself.
+	fn refund_unused_period(subscription_id: SubscriptionId, subscription: &Subscription<T>, refund: BalanceOf<T>) {
+		let patron_wallet = Self::resolve_patron_wallet(subscription);
+
+		let refunded = if let Some(mut pending) = Self::pending_release_by_subscription(subscription_id) {
+			let refund = refund.min(pending.amount);
+			let ok = <T as pallet_utils::Trait>::Currency::transfer(
+				&Self::escrow_account(subscription_id),
+				&patron_wallet,
+				refund,
+				ExistenceRequirement::AllowDeath
+			).is_ok();
+
+			if ok {
+				pending.amount = pending.amount.saturating_sub(refund);
+				if pending.amount.is_zero() {
+					PendingReleaseBySubscription::<T>::remove(subscription_id);
+				} else {
+					PendingReleaseBySubscription::<T>::insert(subscription_id, pending);
+				}
+			}
+
+			if ok { Some(refund) } else { None }
+		} else {
+			Self::plan_by_id(subscription.plan_id).and_then(|plan| Self::resolve_recipient(&plan)).and_then(|recipient| {
+				let ok = <T as pallet_utils::Trait>::Currency::transfer(
+					&recipient,
+					&patron_wallet,
+					refund,
+					ExistenceRequirement::AllowDeath
+				).is_ok();
+
+				if ok { Some(refund) } else { None }
+			})
+		};
+
+		if let Some(refund) = refunded {
+			Self::deposit_event(RawEvent::SubscriptionRefunded(subscription_id, refund));
+		}
+	}
+
+	/// Charges a single due subscription, settling `plan.price` from the patron's
+	/// resolved wallet, then either reschedules it for its next period or deactivates
+	/// it if the transfer failed.
+	fn charge_subscription(subscription_id: SubscriptionId, now: T::BlockNumber) {
+		let subscription = match Self::subscription_by_id(subscription_id) {
+			Some(subscription) if subscription.is_active => subscription,
+			_ => return,
+		};
+
+		let plan = match Self::plan_by_id(subscription.plan_id) {
+			Some(plan) if plan.is_active => plan,
+			_ => return,
+		};
+
+		let patron = Self::resolve_patron_wallet(&subscription);
+		let recipient = Self::resolve_recipient(&plan);
+
+		let charged = recipient.map(|recipient| {
+			Self::settle_payment(
+				subscription_id,
+				&patron,
+				recipient,
+				plan.price,
+				plan.release_condition.clone()
+			)
+		});
+
+		match charged {
+			Some(Ok(())) => {
+				let next_charge_at = now + Self::period_in_blocks(&plan.period);
+				let mut subscription = subscription;
+				subscription.next_charge_at = next_charge_at;
+				SubscriptionById::<T>::insert(subscription_id, subscription);
+				Self::schedule_subscription(subscription_id, next_charge_at);
+			},
+			_ => {
+				let mut subscription = subscription;
+				subscription.is_active = false;
+				SubscriptionById::<T>::insert(subscription_id, subscription);
+				Self::deposit_event(RawEvent::SubscriptionDeactivated(subscription_id));
+			},
+		}
+	}
+
+	/// Returns every subscription plan offered by `space_id`, for rendering a creator's
+	/// monetization offerings on a front-end.
+	pub fn get_plans_by_space(space_id: SpaceId) -> Vec<SubscriptionPlan<T>> {
+		Self::plan_ids_by_space(space_id).iter()
+			.filter_map(Self::plan_by_id)
+			.collect()
+	}
+
+	/// Returns every subscription in `account`'s patron index, for rendering their billing
+	/// status. This includes subscriptions deactivated by a failed recurring charge;
+	/// subscriptions cancelled via `unsubscribe` are removed from the index and not returned.
+	pub fn get_subscriptions_by_patron(account: T::AccountId) -> Vec<Subscription<T>> {
+		Self::subscription_ids_by_patron(account).iter()
+			.filter_map(Self::subscription_by_id)
+			.collect()
+	}
+
+	/// Returns the block at which `subscription_id` will next be charged, or `None` if
+	/// it doesn't exist or is no longer active.
+	pub fn get_next_charge(subscription_id: SubscriptionId) -> Option<T::BlockNumber> {
+		Self::subscription_by_id(subscription_id)
+			.filter(|subscription| subscription.is_active)
+			.map(|subscription| subscription.next_charge_at)
+	}
+
+	/// The weight of a single `charge_subscription` call: reads of the subscription, its
+	/// plan, and the patron/recipient wallet resolution, writes of `SubscriptionById` and
+	/// the due-subscriptions reschedule, plus the `Currency::transfer` it performs.
+	fn charge_subscription_weight() -> Weight {
+		T::DbWeight::get().reads_writes(6, 3) + 50_000
+	}
+
+	/// Drains the subscriptions due at `block`, charging up to `MaxChargesPerBlock` of them
+	/// and carrying any remainder over to the next block so a single block's weight stays
+	/// bounded regardless of how many subscriptions share a due date.
+	pub fn process_due_subscriptions(block: T::BlockNumber) -> Weight {
+		let due = DueSubscriptionsAt::<T>::take(block);
+		let max_charges = T::MaxChargesPerBlock::get() as usize;
+
+		let (to_charge, remainder) = if due.len() > max_charges {
+			due.split_at(max_charges)
+		} else {
+			(&due[..], &due[..0])
+		};
+
+		if !remainder.is_empty() {
+			let next_block = block + T::BlockNumber::from(1u32);
+			for subscription_id in remainder {
+				SubscriptionById::<T>::mutate(subscription_id, |maybe_subscription| {
+					if let Some(subscription) = maybe_subscription {
+						subscription.next_charge_at = next_block;
+					}
+				});
+			}
+			DueSubscriptionsAt::<T>::mutate(next_block, |ids| {
+				ids.extend_from_slice(remainder);
+			});
+		}
+
+		for subscription_id in to_charge {
+			Self::charge_subscription(*subscription_id, block);
+		}
+
+		T::DbWeight::get().reads_writes(1, 1)
+			+ Self::charge_subscription_weight() * (to_charge.len() as Weight)
+			+ T::DbWeight::get().reads_writes(1, 2) * (remainder.len() as Weight)
+	}
+}
@@ -3,11 +3,14 @@
 use codec::{Encode, Decode};
 use sp_std::prelude::*;
 use sp_runtime::RuntimeDebug;
+use sp_runtime::traits::{Saturating, Zero};
 
 use frame_support::{
 	decl_module, decl_storage, decl_event, decl_error, ensure,
 	dispatch::DispatchResult,
-	traits::{Get, Currency, ExistenceRequirement}
+	traits::{Get, Currency, ExistenceRequirement},
+	weights::Weight,
+	ModuleId,
 };
 use frame_system::{self as system, ensure_signed};
 
@@ -35,6 +38,47 @@ pub enum SubscriptionPeriod<BlockNumber> {
 	Custom(BlockNumber), // Currently not supported
 }
 
+/// A condition gating when an escrowed subscription payment is released to its recipient.
+/// Modeled as a tiny payment-plan EDSL: a plan either pays out immediately, after a block
+/// height is reached, or once a designated approver signs off on delivery.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+pub enum ReleaseCondition<AccountId, BlockNumber> {
+	Immediate,
+	After(BlockNumber),
+	RequiresSignature(AccountId),
+}
+
+impl<AccountId, BlockNumber> Default for ReleaseCondition<AccountId, BlockNumber> {
+	fn default() -> Self {
+		Self::Immediate
+	}
+}
+
+/// Evidence submitted to [`Module::witness`] that a [`ReleaseCondition`] has been met.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+pub enum ReleaseProof {
+	BlockHeightReached,
+	ApproverSignedOff,
+}
+
+/// A subscription payment held in escrow until its plan's `release_condition` is satisfied.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+pub struct PendingRelease<T: Trait> {
+	pub amount: BalanceOf<T>,
+	pub recipient: T::AccountId,
+	pub condition: ReleaseCondition<T::AccountId, T::BlockNumber>,
+}
+
+pub type WithdrawalId = u64;
+
+/// A withdrawal of accumulated subscription revenue awaiting enough owner approvals.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+pub struct PendingWithdrawal<T: Trait> {
+	pub amount: BalanceOf<T>,
+	pub to: T::AccountId,
+	pub approvals: Vec<T::AccountId>,
+}
+
 #[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
 pub struct SubscriptionPlan<T: Trait> {
 	pub id: SubscriptionPlanId,
@@ -46,6 +90,9 @@ pub struct SubscriptionPlan<T: Trait> {
 	pub period: SubscriptionPeriod<T::BlockNumber>,
 	pub content: Content,
 	pub is_active: bool,
+	pub release_condition: ReleaseCondition<T::AccountId, T::BlockNumber>,
+	// Whether unsubscribing mid-period refunds the unused, prorated portion of `price`.
+	pub refunds_enabled: bool,
 }
 
 #[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
@@ -56,9 +103,10 @@ pub struct Subscription<T: Trait> {
 	pub wallet: Option<T::AccountId>,
 	pub plan_id: SubscriptionPlanId,
 	pub is_active: bool,
+	pub next_charge_at: T::BlockNumber,
 }
 
-type BalanceOf<T> = <<T as pallet_utils::Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+pub type BalanceOf<T> = <<T as pallet_utils::Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
 
 /// The pallet's configuration trait.
 pub trait Trait:
@@ -68,6 +116,19 @@ pub trait Trait:
 {
 	/// The overarching event type.
 	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+	/// How many blocks make up a single day, used to convert a [`SubscriptionPeriod`]
+	/// into a concrete number of blocks.
+	type BlocksPerDay: Get<Self::BlockNumber>;
+
+	/// The maximum number of due subscriptions charged in a single block. Any
+	/// remainder is carried over to the following block to keep `on_initialize`
+	/// weight bounded.
+	type MaxChargesPerBlock: Get<u32>;
+
+	/// The pallet's module id, used to derive escrow accounts that hold payments
+	/// pending release under a [`ReleaseCondition`].
+	type ModuleId: Get<ModuleId>;
 }
 
 decl_storage! {
@@ -95,9 +156,28 @@ decl_storage! {
 		pub SubscriptionIdsBySpace get(fn subscription_ids_by_space):
 			map hasher(twox_64_concat) SpaceId => Vec<SubscriptionId>;
 
-		// todo: this should be used by Scheduler to transfer funds from subscribers' wallets to creator's (space) wallet.
-		pub SubscriptionIdsByPeriod get(fn subscription_ids_by_period):
-			map hasher(twox_64_concat) SubscriptionPeriod<T::BlockNumber> => Vec<SubscriptionId>;
+		// Index of subscriptions due to be charged at a given block, drained by `on_initialize`.
+		pub DueSubscriptionsAt get(fn due_subscriptions_at):
+			map hasher(twox_64_concat) T::BlockNumber => Vec<SubscriptionId>;
+
+		// Payments held in escrow until their plan's release condition is satisfied.
+		pub PendingReleaseBySubscription get(fn pending_release_by_subscription):
+			map hasher(twox_64_concat) SubscriptionId => Option<PendingRelease<T>>;
+
+		// Multisig revenue withdrawals:
+
+		// Owners and confirmation threshold required to withdraw a space's accumulated
+		// subscription revenue. Absent means revenue pays out without multisig gating.
+		pub RecipientMultisig get(fn recipient_multisig):
+			map hasher(twox_64_concat) SpaceId => Option<(Vec<T::AccountId>, u16)>;
+
+		pub NextWithdrawalId get(fn next_withdrawal_id): WithdrawalId = 1;
+
+		pub PendingWithdrawalById get(fn pending_withdrawal_by_id):
+			map hasher(twox_64_concat) WithdrawalId => Option<PendingWithdrawal<T>>;
+
+		pub WithdrawalIdsBySpace get(fn withdrawal_ids_by_space):
+			map hasher(twox_64_concat) SpaceId => Vec<WithdrawalId>;
 
 		// Wallets
 
@@ -114,9 +194,20 @@ decl_storage! {
 // The pallet's events
 decl_event!(
 	pub enum Event<T> where
-		AccountId = <T as system::Trait>::AccountId
+		AccountId = <T as system::Trait>::AccountId,
+		Balance = BalanceOf<T>
 	{
 		SubscriptionPlanCreated(AccountId, SubscriptionPlanId),
+		SubscriptionDeactivated(SubscriptionId),
+		SubscriptionReleased(SubscriptionId),
+		EscrowReclaimed(SubscriptionId),
+		RecipientMultisigUpdated(SpaceId),
+		WithdrawalProposed(SpaceId, WithdrawalId),
+		WithdrawalConfirmed(SpaceId, WithdrawalId, AccountId),
+		WithdrawalExecuted(SpaceId, WithdrawalId),
+		Unsubscribed(SubscriptionId),
+		SubscriptionRefunded(SubscriptionId, Balance),
+		SubscriptionPlanDeleted(SubscriptionPlanId),
 		// todo: complete event list for this pallet once dispatches are implemented
 	}
 );
@@ -131,6 +222,16 @@ decl_error! {
 		RecipientNotFound,
 		SubscriptionNotFound,
 		SubscriptionPlanNotFound,
+		NoPendingRelease,
+		ReleaseConditionNotSatisfied,
+		SubscriptionStillActive,
+		EmptyOwnerList,
+		InvalidConfirmationThreshold,
+		NotMultisigOwner,
+		MultisigNotFound,
+		AlreadyApproved,
+		WithdrawalNotFound,
+		PlanHasActiveSubscriptions,
 	}
 }
 
@@ -143,6 +244,14 @@ decl_module! {
 		// Initializing events
 		fn deposit_event() = default;
 
+		/// Charges up to `MaxChargesPerBlock` of the subscriptions due at `block`, the way
+		/// `subscribe` does for the initial payment, and reschedules each into
+		/// `DueSubscriptionsAt` for its next period; any remainder is carried over to the
+		/// next block. See [`Module::process_due_subscriptions`].
+		fn on_initialize(block: T::BlockNumber) -> Weight {
+			Self::process_due_subscriptions(block)
+		}
+
 		#[weight = T::DbWeight::get().reads_writes(3, 3) + 25_000]
 		pub fn create_plan(
 			origin,
@@ -150,7 +259,9 @@ decl_module! {
 			custom_wallet: Option<T::AccountId>,
 			price: BalanceOf<T>,
 			period: SubscriptionPeriod<T::BlockNumber>,
-			content: Content
+			content: Content,
+			release_condition: ReleaseCondition<T::AccountId, T::BlockNumber>,
+			refunds_enabled: bool
 		) -> DispatchResult {
 			let sender = ensure_signed(origin)?;
 
@@ -162,10 +273,7 @@ decl_module! {
 			);
 
 			let space = Spaces::<T>::require_space(space_id)?;
-			space.ensure_space_owner(sender.clone())?;
-
-			// todo:
-			// 	- use permission to manage (here: create) subscription plans
+			Self::ensure_subscriptions_manager(sender.clone(), &space)?;
 
 			let plan_id = Self::next_plan_id();
 			let subscription_plan = SubscriptionPlan::<T>::new(
@@ -175,7 +283,9 @@ decl_module! {
 				custom_wallet,
 				price,
 				period,
-				content
+				content,
+				release_condition,
+				refunds_enabled
 			);
 
 			PlanById::<T>::insert(plan_id, subscription_plan);
@@ -212,7 +322,7 @@ decl_module! {
 			let sender = ensure_signed(origin)?;
 
 			let space = Spaces::<T>::require_space(space_id)?;
-			space.ensure_space_owner(sender)?;
+			Self::ensure_subscriptions_manager(sender, &space)?;
 
 			if let Some(wallet) = custom_wallet {
 				RecipientWallet::<T>::insert(space.id, wallet);
@@ -223,9 +333,123 @@ decl_module! {
 			Ok(())
 		}
 
-		#[weight = 10_000]
+		/// Designates `owners` and a confirmation `threshold` that must approve any
+		/// withdrawal of `space_id`'s accumulated subscription revenue.
+		#[weight = T::DbWeight::get().reads_writes(1, 1) + 10_000]
+		pub fn set_recipient_multisig(
+			origin,
+			space_id: SpaceId,
+			owners: Vec<T::AccountId>,
+			threshold: u16
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let space = Spaces::<T>::require_space(space_id)?;
+			space.ensure_space_owner(sender)?;
+
+			ensure!(!owners.is_empty(), Error::<T>::EmptyOwnerList);
+			ensure!(
+				threshold >= 1 && threshold as usize <= owners.len(),
+				Error::<T>::InvalidConfirmationThreshold
+			);
+
+			RecipientMultisig::<T>::insert(space_id, (owners, threshold));
+			Self::deposit_event(RawEvent::RecipientMultisigUpdated(space_id));
+
+			Ok(())
+		}
+
+		/// Proposes withdrawing `amount` of `space_id`'s accumulated revenue to `to`.
+		/// Callable by any of the space's listed multisig owners.
+		#[weight = T::DbWeight::get().reads_writes(2, 2) + 25_000]
+		pub fn propose_withdrawal(
+			origin,
+			space_id: SpaceId,
+			amount: BalanceOf<T>,
+			to: T::AccountId
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let (owners, _threshold) = Self::recipient_multisig(space_id)
+				.ok_or(Error::<T>::MultisigNotFound)?;
+			ensure!(owners.contains(&sender), Error::<T>::NotMultisigOwner);
+
+			let withdrawal_id = Self::next_withdrawal_id();
+			let withdrawal = PendingWithdrawal::<T> {
+				amount,
+				to,
+				approvals: vec![sender],
+			};
+
+			PendingWithdrawalById::<T>::insert(withdrawal_id, withdrawal);
+			WithdrawalIdsBySpace::mutate(space_id, |ids| ids.push(withdrawal_id));
+			NextWithdrawalId::mutate(|x| { *x += 1 });
+			Self::deposit_event(RawEvent::WithdrawalProposed(space_id, withdrawal_id));
+
+			Ok(())
+		}
+
+		/// Adds the caller's approval to a pending withdrawal, executing the transfer
+		/// from the space's revenue account once enough owners have confirmed it.
+		#[weight = T::DbWeight::get().reads_writes(2, 2) + 25_000]
+		pub fn confirm_withdrawal(
+			origin,
+			space_id: SpaceId,
+			withdrawal_id: WithdrawalId
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let (owners, threshold) = Self::recipient_multisig(space_id)
+				.ok_or(Error::<T>::MultisigNotFound)?;
+			ensure!(owners.contains(&sender), Error::<T>::NotMultisigOwner);
+
+			ensure!(
+				Self::withdrawal_ids_by_space(space_id).contains(&withdrawal_id),
+				Error::<T>::WithdrawalNotFound
+			);
+			let mut withdrawal = Self::pending_withdrawal_by_id(withdrawal_id)
+				.ok_or(Error::<T>::WithdrawalNotFound)?;
+			ensure!(!withdrawal.approvals.contains(&sender), Error::<T>::AlreadyApproved);
+
+			withdrawal.approvals.push(sender.clone());
+			Self::deposit_event(RawEvent::WithdrawalConfirmed(space_id, withdrawal_id, sender));
+
+			if withdrawal.approvals.len() >= threshold as usize {
+				<T as pallet_utils::Trait>::Currency::transfer(
+					&Self::revenue_account(space_id),
+					&withdrawal.to,
+					withdrawal.amount,
+					ExistenceRequirement::AllowDeath
+				)?;
+
+				PendingWithdrawalById::<T>::remove(withdrawal_id);
+				WithdrawalIdsBySpace::mutate(space_id, |ids| ids.retain(|id| *id != withdrawal_id));
+				Self::deposit_event(RawEvent::WithdrawalExecuted(space_id, withdrawal_id));
+			} else {
+				PendingWithdrawalById::<T>::insert(withdrawal_id, withdrawal);
+			}
+
+			Ok(())
+		}
+
+		/// Removes a plan, as long as no active subscriptions still depend on it.
+		#[weight = T::DbWeight::get().reads_writes(3, 2) + 25_000]
 		pub fn delete_plan(origin, plan_id: SubscriptionPlanId) -> DispatchResult {
-			let _ = ensure_signed(origin)?;
+			let sender = ensure_signed(origin)?;
+
+			let plan = Self::require_plan(plan_id)?;
+			let space = Spaces::<T>::require_space(plan.space_id)?;
+			Self::ensure_subscriptions_manager(sender, &space)?;
+
+			let has_active_subscriptions = Self::subscription_ids_by_space(plan.space_id).iter().any(|id| {
+				Self::subscription_by_id(*id).map_or(false, |s| s.plan_id == plan_id && s.is_active)
+			});
+			ensure!(!has_active_subscriptions, Error::<T>::PlanHasActiveSubscriptions);
+
+			PlanById::<T>::remove(plan_id);
+			PlanIdsBySpace::mutate(plan.space_id, |ids| ids.retain(|id| *id != plan_id));
+			Self::deposit_event(RawEvent::SubscriptionPlanDeleted(plan_id));
+
 			Ok(())
 		}
 
@@ -246,33 +470,35 @@ decl_module! {
 				}
 				false
 			});
-			ensure!(is_already_subscribed, Error::<T>::AlreadySubscribed);
+			ensure!(!is_already_subscribed, Error::<T>::AlreadySubscribed);
+
+			let recipient = Self::resolve_recipient(&plan);
+			ensure!(recipient.is_some(), Error::<T>::RecipientNotFound);
 
 			let subscription_id = Self::next_subscription_id();
-			let subscription = Subscription::<T>::new(
+			Self::settle_payment(
 				subscription_id,
-				sender.clone(),
-				custom_wallet,
-				plan_id
-			);
-
-			let recipient = plan.wallet.clone()
-				.or_else(|| Self::recipient_wallet(plan.space_id))
-				.or_else(|| {
-					Spaces::<T>::require_space(plan.space_id).map(|space| space.owner).ok()
-				});
-
-			ensure!(recipient.is_some(), Error::<T>::RecipientNotFound);
-			<T as pallet_utils::Trait>::Currency::transfer(
 				&sender,
-				&recipient.unwrap(),
+				recipient.unwrap(),
 				plan.price,
-				ExistenceRequirement::KeepAlive
+				plan.release_condition.clone()
 			)?;
 
-			// todo: schedule recurrent payment
+			let now = <system::Module<T>>::block_number();
+			let next_charge_at = now + Self::period_in_blocks(&plan.period);
+			let subscription = Subscription::<T>::new(
+				subscription_id,
+				sender.clone(),
+				custom_wallet,
+				plan_id,
+				next_charge_at
+			);
 
 			SubscriptionById::<T>::insert(subscription_id, subscription);
+			SubscriptionIdsByPatron::<T>::mutate(&sender, |ids| ids.push(subscription_id));
+			SubscriptionIdsBySpace::mutate(plan.space_id, |ids| ids.push(subscription_id));
+			Self::schedule_subscription(subscription_id, next_charge_at);
+			NextSubscriptionId::mutate(|x| { *x += 1 });
 
 			Ok(())
 		}
@@ -314,10 +540,95 @@ decl_module! {
 			Ok(())
 		}
 
-		#[weight = 10_000]
-		pub fn unsubscribe(origin, plan_id: SubscriptionPlanId) -> DispatchResult {
-			// todo(i): maybe we need here subscription_id, not plan_id?
-			let _ = ensure_signed(origin)?;
+		/// Cancels a subscription, removing it from every index it was scheduled in and,
+		/// if its plan has `refunds_enabled`, refunding the unused portion of the current
+		/// period's payment.
+		#[weight = T::DbWeight::get().reads_writes(5, 5) + 50_000]
+		pub fn unsubscribe(origin, subscription_id: SubscriptionId) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let mut subscription = Self::require_subscription(subscription_id)?;
+			subscription.ensure_subscriber(&sender)?;
+
+			subscription.is_active = false;
+			SubscriptionById::<T>::insert(subscription_id, subscription.clone());
+
+			SubscriptionIdsByPatron::<T>::mutate(&sender, |ids| ids.retain(|id| *id != subscription_id));
+			DueSubscriptionsAt::<T>::mutate(subscription.next_charge_at, |ids| ids.retain(|id| *id != subscription_id));
+
+			if let Some(plan) = Self::plan_by_id(subscription.plan_id) {
+				SubscriptionIdsBySpace::mutate(plan.space_id, |ids| ids.retain(|id| *id != subscription_id));
+
+				let now = <system::Module<T>>::block_number();
+				if plan.refunds_enabled && subscription.next_charge_at > now {
+					let period_in_blocks = Self::period_in_blocks(&plan.period);
+					let remaining = subscription.next_charge_at - now;
+					let refund = Self::prorated_amount(plan.price, remaining, period_in_blocks);
+
+					if !refund.is_zero() {
+						Self::refund_unused_period(subscription_id, &subscription, refund);
+					}
+				}
+			}
+
+			Self::deposit_event(RawEvent::Unsubscribed(subscription_id));
+
+			Ok(())
+		}
+
+		/// Releases an escrowed subscription payment to its recipient once `proof`
+		/// demonstrates that the plan's `release_condition` has been satisfied.
+		#[weight = T::DbWeight::get().reads_writes(2, 1) + 25_000]
+		pub fn witness(origin, subscription_id: SubscriptionId, proof: ReleaseProof) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let pending = Self::require_pending_release(subscription_id)?;
+			let is_satisfied = match (&pending.condition, &proof) {
+				(ReleaseCondition::After(height), ReleaseProof::BlockHeightReached) =>
+					<system::Module<T>>::block_number() >= *height,
+				(ReleaseCondition::RequiresSignature(approver), ReleaseProof::ApproverSignedOff) =>
+					sender == *approver,
+				_ => false,
+			};
+			ensure!(is_satisfied, Error::<T>::ReleaseConditionNotSatisfied);
+
+			<T as pallet_utils::Trait>::Currency::transfer(
+				&Self::escrow_account(subscription_id),
+				&pending.recipient,
+				pending.amount,
+				ExistenceRequirement::AllowDeath
+			)?;
+
+			PendingReleaseBySubscription::<T>::remove(subscription_id);
+			Self::deposit_event(RawEvent::SubscriptionReleased(subscription_id));
+
+			Ok(())
+		}
+
+		/// Returns an escrowed payment to the patron once its subscription has been
+		/// cancelled (via `unsubscribe`) or deactivated (after a failed recurring charge)
+		/// without the release condition ever being satisfied. A still-active subscription
+		/// is expected to reach release through `witness` instead.
+		#[weight = T::DbWeight::get().reads_writes(3, 1) + 25_000]
+		pub fn reclaim(origin, subscription_id: SubscriptionId) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+
+			let subscription = Self::require_subscription(subscription_id)?;
+			subscription.ensure_subscriber(&sender)?;
+
+			let pending = Self::require_pending_release(subscription_id)?;
+			ensure!(!subscription.is_active, Error::<T>::SubscriptionStillActive);
+
+			<T as pallet_utils::Trait>::Currency::transfer(
+				&Self::escrow_account(subscription_id),
+				&sender,
+				pending.amount,
+				ExistenceRequirement::AllowDeath
+			)?;
+
+			PendingReleaseBySubscription::<T>::remove(subscription_id);
+			Self::deposit_event(RawEvent::EscrowReclaimed(subscription_id));
+
 			Ok(())
 		}
 	}